@@ -1,10 +1,10 @@
 #![feature(collections, convert)]
 
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, Read};
-use std::mem;
-use std::slice;
 use std::path::Path;
 
 pub trait ReadExt: Read {
@@ -13,18 +13,112 @@ pub trait ReadExt: Read {
         let len = try!(self.read(buf));
         self.read_at_least(&mut buf[len..])
     }
+}
+
+impl<R: Read> ReadExt for R {}
 
-    fn read_binary<T: Copy>(&mut self) -> io::Result<T> {
-        let mut loaded = unsafe { mem::uninitialized::<T>() };
-        let size = mem::size_of::<T>();
-        try!(self.read_at_least(unsafe {
-            slice::from_raw_parts_mut(&mut loaded as *mut _ as *mut u8, size)
-        }));
-        Ok(loaded)
+#[derive(Debug)]
+pub struct BinaryError {
+    offset: usize,
+    size: usize,
+    len: usize,
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "tried to read {} byte(s) at offset {} from a buffer of \
+                    length {}", self.size, self.offset, self.len)
     }
 }
 
-impl<R: Read> ReadExt for R {}
+impl Error for BinaryError {
+    fn description(&self) -> &str { "unexpected end of binary data" }
+}
+
+pub type BinaryResult<T> = Result<T, BinaryError>;
+
+// Bounds-checked, little-endian accessors over a raw byte slice.
+pub trait ByteBufferExt {
+    fn bytes(&self) -> &[u8];
+
+    fn check_range(&self, offset: usize, size: usize) -> BinaryResult<()> {
+        let len = self.bytes().len();
+        if offset + size > len {
+            Err(BinaryError { offset: offset, size: size, len: len })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn c_u8(&self, offset: usize) -> BinaryResult<u8> {
+        try!(self.check_range(offset, 1));
+        Ok(self.bytes()[offset])
+    }
+
+    fn c_u16le(&self, offset: usize) -> BinaryResult<u16> {
+        try!(self.check_range(offset, 2));
+        let b = self.bytes();
+        Ok(((b[offset + 1] as u16) << 8) | (b[offset] as u16))
+    }
+
+    fn c_i16le(&self, offset: usize) -> BinaryResult<i16> {
+        self.c_u16le(offset).map(|value| value as i16)
+    }
+
+    fn c_u32le(&self, offset: usize) -> BinaryResult<u32> {
+        try!(self.check_range(offset, 4));
+        let b = self.bytes();
+        Ok(((b[offset + 3] as u32) << 24) | ((b[offset + 2] as u32) << 16) |
+           ((b[offset + 1] as u32) << 8) | (b[offset] as u32))
+    }
+
+    fn c_i32le(&self, offset: usize) -> BinaryResult<i32> {
+        self.c_u32le(offset).map(|value| value as i32)
+    }
+
+    fn c_name(&self, offset: usize, size: usize) -> BinaryResult<&[u8]> {
+        try!(self.check_range(offset, size));
+        Ok(&self.bytes()[offset .. offset + size])
+    }
+}
+
+impl ByteBufferExt for [u8] {
+    fn bytes(&self) -> &[u8] { self }
+}
+
+pub trait FromBytes: Sized {
+    fn size() -> usize;
+    fn from_bytes(bytes: &[u8]) -> BinaryResult<Self>;
+}
+
+impl FromBytes for u8 {
+    fn size() -> usize { 1 }
+
+    fn from_bytes(bytes: &[u8]) -> BinaryResult<u8> {
+        bytes.c_u8(0)
+    }
+}
+
+// The encoding counterpart to `FromBytes`.
+pub trait ToBytes {
+    fn to_bytes(&self, out: &mut Vec<u8>);
+}
+
+impl ToBytes for u8 {
+    fn to_bytes(&self, out: &mut Vec<u8>) { out.push(*self); }
+}
+
+pub trait ReadBinaryExt: Read {
+    fn read_binary<T: FromBytes>(&mut self) -> io::Result<T> {
+        let mut buf = vec![0u8; T::size()];
+        try!(self.read_at_least(&mut buf));
+        T::from_bytes(&buf).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}", e))
+        })
+    }
+}
+
+impl<R: Read> ReadBinaryExt for R {}
 
 pub fn read_utf8_file<P: AsRef<Path>>(path: &P) -> Result<String, String> {
     File::open(path.as_ref())
@@ -40,3 +134,152 @@ pub fn read_utf8_file<P: AsRef<Path>>(path: &P) -> Result<String, String> {
         })
 }
 
+pub struct ShaderIncludes {
+    sources: HashMap<String, String>,
+}
+
+impl ShaderIncludes {
+    pub fn new() -> ShaderIncludes {
+        ShaderIncludes { sources: HashMap::new() }
+    }
+
+    pub fn add(&mut self, name: &str, source: &str) -> &mut ShaderIncludes {
+        self.sources.insert(name.to_string(), source.to_string());
+        self
+    }
+}
+
+// Line-offset map back to the original file, so a GLSL compile error on
+// the spliced-together source can still be reported against the file and
+// line the user actually wrote.
+pub struct SourceLine {
+    pub file: String,
+    pub line: usize,
+}
+
+pub struct PreprocessedShader {
+    pub source: String,
+    pub line_map: Vec<SourceLine>,
+}
+
+pub fn preprocess_shader<P: AsRef<Path>>(path: &P, includes: &ShaderIncludes)
+        -> Result<PreprocessedShader, String> {
+    let mut shader = PreprocessedShader { source: String::new(), line_map: Vec::new() };
+    let mut stack = Vec::new();
+    let root = path.as_ref().to_string_lossy().into_owned();
+    try!(expand_includes(&root, includes, &mut stack, &mut shader));
+    Ok(shader)
+}
+
+fn expand_includes(name: &str, includes: &ShaderIncludes, stack: &mut Vec<String>,
+                    shader: &mut PreprocessedShader) -> Result<(), String> {
+    if stack.iter().any(|included| included == name) {
+        stack.push(name.to_string());
+        return Err(format!("Cyclic #include: {}", stack.join(" -> ")));
+    }
+
+    let source = try!(load_include_source(name, includes));
+
+    stack.push(name.to_string());
+    for (line_index, line) in source.lines().enumerate() {
+        match parse_include_directive(line) {
+            Some(included_name) =>
+                try!(expand_includes(&included_name, includes, stack, shader)),
+            None => {
+                shader.source.push_str(line);
+                shader.source.push('\n');
+                shader.line_map.push(
+                    SourceLine { file: name.to_string(), line: line_index + 1 });
+            }
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+fn load_include_source(name: &str, includes: &ShaderIncludes)
+        -> Result<String, String> {
+    match includes.sources.get(name) {
+        Some(source) => Ok(source.clone()),
+        None => read_utf8_file(&name),
+    }
+}
+
+fn parse_include_directive(line: &str) -> Option<String> {
+    let line = line.trim();
+    if !line.starts_with("#include") {
+        return None;
+    }
+    let rest = line["#include".len()..].trim();
+    if rest.len() >= 2 && rest.starts_with('"') && rest.ends_with('"') {
+        Some(rest[1 .. rest.len() - 1].to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn test_preprocess_shader_inlines_registered_include() {
+        let mut includes = ShaderIncludes::new();
+        includes.add("light.glsl", "vec3 get_light() { return vec3(1.0); }\n");
+
+        let path = env::temp_dir().join("rust_doom_test_preprocess_shader.glsl");
+        {
+            let mut file = File::create(&path).unwrap();
+            file.write_all(
+                b"#version 150\n#include \"light.glsl\"\nvoid main() {}\n").unwrap();
+        }
+
+        let shader = preprocess_shader(&path, &includes).unwrap();
+
+        assert!(shader.source.contains("get_light"));
+        assert_eq!(shader.line_map.len(), 3);
+        assert_eq!(shader.line_map[0].line, 1);
+        assert_eq!(shader.line_map[1].file, "light.glsl");
+        assert_eq!(shader.line_map[2].line, 3);
+    }
+
+    #[test]
+    fn test_preprocess_shader_detects_cycles() {
+        let mut includes = ShaderIncludes::new();
+        includes.add("a.glsl", "#include \"b.glsl\"\n");
+        includes.add("b.glsl", "#include \"a.glsl\"\n");
+
+        assert!(preprocess_shader(&"a.glsl", &includes).is_err());
+    }
+
+    #[test]
+    fn test_c_u8_bounds_check() {
+        let buf: &[u8] = &[0x42];
+        assert_eq!(buf.c_u8(0).unwrap(), 0x42);
+        assert!(buf.c_u8(1).is_err());
+    }
+
+    #[test]
+    fn test_c_u16le_decodes_little_endian() {
+        let buf: &[u8] = &[0x34, 0x12];
+        assert_eq!(buf.c_u16le(0).unwrap(), 0x1234);
+        assert!(buf.c_u16le(1).is_err());
+    }
+
+    #[test]
+    fn test_c_i32le_decodes_little_endian() {
+        let buf: &[u8] = &[0xff, 0xff, 0xff, 0xff];
+        assert_eq!(buf.c_i32le(0).unwrap(), -1);
+        assert!(buf.c_i32le(1).is_err());
+    }
+
+    #[test]
+    fn test_c_name_truncated_buffer_errors() {
+        let buf: &[u8] = b"THIN";
+        assert!(buf.c_name(0, 8).is_err());
+        assert_eq!(buf.c_name(0, 4).unwrap(), b"THIN");
+    }
+}
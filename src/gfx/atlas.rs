@@ -0,0 +1,182 @@
+use math::Vec2f;
+use std::rc::Rc;
+use std::vec::Vec;
+use texture::Texture;
+
+#[derive(Copy, Clone)]
+pub struct AtlasEntry {
+    pub page: usize,
+    pub uv_min: Vec2f,
+    pub uv_max: Vec2f,
+}
+
+// A horizontal strip starting at `y`, `height` tall, filled up to `next_x`.
+struct Shelf {
+    y: u32,
+    height: u32,
+    next_x: u32,
+}
+
+// The rectangle-packing algorithm itself, kept free of any GPU dependency
+// so it can be unit tested without a GL context.
+struct ShelfPacker {
+    width: u32,
+    height: u32,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    fn new(width: u32, height: u32) -> ShelfPacker {
+        ShelfPacker { width: width, height: height, shelves: Vec::new() }
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        for shelf in self.shelves.iter_mut() {
+            if height <= shelf.height && shelf.next_x + width <= self.width {
+                let x = shelf.next_x;
+                shelf.next_x += width;
+                return Some((x, shelf.y));
+            }
+        }
+
+        let y = self.shelves.iter()
+            .map(|shelf| shelf.y + shelf.height)
+            .max()
+            .unwrap_or(0);
+        if y + height > self.height {
+            return None;
+        }
+        self.shelves.push(Shelf { y: y, height: height, next_x: width });
+        Some((0, y))
+    }
+}
+
+struct Page {
+    texture: Rc<Texture>,
+    width: u32,
+    height: u32,
+    packer: ShelfPacker,
+}
+
+impl Page {
+    fn new(width: u32, height: u32) -> Page {
+        Page {
+            texture: Rc::new(Texture::new(width, height)),
+            width: width,
+            height: height,
+            packer: ShelfPacker::new(width, height),
+        }
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        self.packer.allocate(width, height)
+    }
+
+    fn entry(&self, page_index: usize, x: u32, y: u32, width: u32, height: u32)
+            -> AtlasEntry {
+        AtlasEntry {
+            page: page_index,
+            uv_min: Vec2f::new(x as f32 / self.width as f32,
+                                y as f32 / self.height as f32),
+            uv_max: Vec2f::new((x + width) as f32 / self.width as f32,
+                                (y + height) as f32 / self.height as f32),
+        }
+    }
+}
+
+pub struct Atlas {
+    page_width: u32,
+    page_height: u32,
+    pages: Vec<Page>,
+}
+
+impl Atlas {
+    pub fn new(page_width: u32, page_height: u32) -> Atlas {
+        Atlas { page_width: page_width, page_height: page_height, pages: Vec::new() }
+    }
+
+    pub fn add(&mut self, width: u32, height: u32, data: &[u8]) -> AtlasEntry {
+        for (page_index, page) in self.pages.iter_mut().enumerate() {
+            if let Some((x, y)) = page.allocate(width, height) {
+                page.texture.update_region(x, y, width, height, data);
+                return page.entry(page_index, x, y, width, height);
+            }
+        }
+
+        assert!(width <= self.page_width && height <= self.page_height,
+                "Sub-image {}x{} does not fit a {}x{} atlas page.",
+                width, height, self.page_width, self.page_height);
+
+        let mut page = Page::new(self.page_width, self.page_height);
+        let (x, y) = page.allocate(width, height)
+            .expect("Freshly allocated atlas page cannot fit sub-image.");
+        page.texture.update_region(x, y, width, height, data);
+        let page_index = self.pages.len();
+        let entry = page.entry(page_index, x, y, width, height);
+        self.pages.push(page);
+        entry
+    }
+
+    pub fn num_pages(&self) -> usize { self.pages.len() }
+
+    pub fn page_texture(&self, page_index: usize) -> Rc<Texture> {
+        self.pages[page_index].texture.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_packs_shelf_exactly() {
+        let mut packer = ShelfPacker::new(64, 64);
+        assert_eq!(packer.allocate(64, 16), Some((0, 0)));
+        assert_eq!(packer.allocate(64, 16), Some((0, 16)));
+    }
+
+    #[test]
+    fn test_allocate_fills_shelf_left_to_right() {
+        let mut packer = ShelfPacker::new(64, 64);
+        assert_eq!(packer.allocate(16, 8), Some((0, 0)));
+        assert_eq!(packer.allocate(16, 8), Some((16, 0)));
+        assert_eq!(packer.allocate(16, 8), Some((32, 0)));
+    }
+
+    #[test]
+    fn test_allocate_starts_new_shelf_when_too_tall() {
+        let mut packer = ShelfPacker::new(64, 64);
+        assert_eq!(packer.allocate(16, 8), Some((0, 0)));
+        assert_eq!(packer.allocate(16, 16), Some((0, 8)));
+    }
+
+    #[test]
+    fn test_allocate_overflow_returns_none() {
+        let mut packer = ShelfPacker::new(32, 32);
+        assert_eq!(packer.allocate(32, 16), Some((0, 0)));
+        assert_eq!(packer.allocate(32, 16), Some((0, 16)));
+        assert_eq!(packer.allocate(32, 1), None);
+    }
+
+    #[test]
+    fn test_allocate_rejects_too_wide_for_shelf() {
+        // The lone shelf spans the full page height, so an item that
+        // doesn't fit it can't fall through to a new shelf either.
+        let mut packer = ShelfPacker::new(32, 32);
+        assert_eq!(packer.allocate(24, 32), Some((0, 0)));
+        assert_eq!(packer.allocate(16, 8), None);
+    }
+
+    #[test]
+    fn test_allocate_multi_page_rollover() {
+        // Mirrors what `Atlas::add` does when the first page fills up: a
+        // second, independent packer is used once the first rejects.
+        let mut page0 = ShelfPacker::new(32, 32);
+        let mut page1 = ShelfPacker::new(32, 32);
+
+        assert_eq!(page0.allocate(32, 32), Some((0, 0)));
+        assert_eq!(page0.allocate(1, 1), None);
+
+        assert_eq!(page1.allocate(1, 1), Some((0, 0)));
+    }
+}
@@ -1,3 +1,4 @@
+use atlas::Atlas;
 use gl;
 use gl::types::GLenum;
 use math::{Mat4, Vec2f};
@@ -85,6 +86,22 @@ impl RenderStep {
         self
     }
 
+    // Binds every page of `atlas` as a shared texture starting at GL
+    // texture unit `first_unit`, exposed to the shader via `name[0]`,
+    // `name[1]`, ... sampler uniforms.
+    pub fn add_atlas(&mut self, name: &str, atlas: &Atlas, first_unit: usize)
+            -> &mut RenderStep {
+        for page_index in 0 .. atlas.num_pages() {
+            let unit = first_unit + page_index;
+            let uniform = self.shader.expect_uniform(
+                &format!("{}[{}]", name, page_index));
+            self.shader.bind_mut().set_uniform_i32(uniform, unit as i32).unbind();
+            self.shared_tex.push(
+                (unit as GLenum + gl::TEXTURE0, atlas.page_texture(page_index)));
+        }
+        self
+    }
+
     pub fn add_static_vbo(&mut self, vbo: VertexBuffer) -> &mut RenderStep {
         self.static_vbos.push(vbo);
         self
@@ -2,18 +2,18 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs::File;
 use std::io::{Seek, SeekFrom};
-use std::mem;
-use std::slice;
 use std::vec::Vec;
 use std::path::Path;
 
-use super::base::ReadExt;
+use super::base::{FromBytes, ReadBinaryExt, ReadExt, ToBytes};
+use super::cache::LumpCache;
 use super::meta::WadMetadata;
 use super::types::{WadLump, WadInfo, WadName, WadNameCast};
 use super::util::wad_type_from_info;
 
 pub struct Archive {
     file: File,
+    identification: [u8; 4],
     index_map: HashMap<WadName, usize>,
     lumps: Vec<LumpInfo>,
     levels: Vec<usize>,
@@ -67,11 +67,14 @@ impl Archive {
         Ok(Archive {
             meta: meta,
             file: file,
+            identification: header.identification,
             lumps: lumps,
             index_map: index_map,
             levels: levels })
     }
 
+    pub fn identification(&self) -> [u8; 4] { self.identification }
+
     pub fn num_levels(&self) -> usize { self.levels.len() }
 
     pub fn get_level_lump_index(&self, level_index: usize) -> usize {
@@ -96,34 +99,64 @@ impl Archive {
         self.lumps[lump_index].size == 0
     }
 
-    pub fn read_lump_by_name<T: Copy>(&mut self, name: &WadName) -> Vec<T> {
+    pub fn read_lump_by_name<T: FromBytes>(&mut self, name: &WadName) -> Vec<T> {
         let index = self.get_lump_index(name).unwrap_or_else(
             || panic!("No such lump '{}'", name));
         self.read_lump(index)
     }
 
-    pub fn read_lump<T: Copy>(&mut self, index: usize) -> Vec<T> {
+    pub fn read_lump<T: FromBytes>(&mut self, index: usize) -> Vec<T> {
         let info = self.lumps[index];
+        let elem_size = T::size();
         assert!(info.size > 0);
-        assert!(info.size % mem::size_of::<T>() == 0);
-        let num_elems = info.size / mem::size_of::<T>();
-        let mut buf = Vec::with_capacity(num_elems);
+        assert!(info.size % elem_size == 0);
+        let num_elems = info.size / elem_size;
+
+        let mut buf = vec![0u8; info.size];
         self.file.seek(SeekFrom::Start(info.offset)).unwrap();
-        unsafe {
-            buf.set_len(num_elems);
-            self.file.read_at_least(slice::from_raw_parts_mut(
-                    (buf.as_mut_ptr() as *mut u8), info.size)).unwrap();
-        }
-        buf
+        self.file.read_at_least(&mut buf).unwrap();
+
+        (0 .. num_elems).map(|i_elem| {
+            let start = i_elem * elem_size;
+            T::from_bytes(&buf[start .. start + elem_size]).unwrap_or_else(|e| {
+                panic!("Corrupt lump '{}': {}", info.name, e)
+            })
+        }).collect()
     }
 
-    pub fn read_lump_single<T: Copy>(&mut self, index: usize) -> T {
+    pub fn read_lump_single<T: FromBytes>(&mut self, index: usize) -> T {
         let info = self.lumps[index];
-        assert!(info.size == mem::size_of::<T>());
+        assert!(info.size == T::size());
         self.file.seek(SeekFrom::Start(info.offset)).unwrap();
         self.file.read_binary().unwrap()
     }
 
+    // Like `read_lump`, but consults `cache` first, keyed by the lump's raw
+    // bytes and `decoder_version`; on a miss the decoded result is stored
+    // back so the next run of the same WAD skips re-decoding it.
+    pub fn read_lump_cached<T: FromBytes + ToBytes>(&mut self, index: usize,
+                                                     cache: &LumpCache,
+                                                     decoder_version: u32) -> Vec<T> {
+        let info = self.lumps[index];
+        let elem_size = T::size();
+        assert!(info.size > 0);
+        assert!(info.size % elem_size == 0);
+
+        let mut buf = vec![0u8; info.size];
+        self.file.seek(SeekFrom::Start(info.offset)).unwrap();
+        self.file.read_at_least(&mut buf).unwrap();
+
+        cache.get_or_decode(&buf, decoder_version, || {
+            let num_elems = info.size / elem_size;
+            (0 .. num_elems).map(|i_elem| {
+                let start = i_elem * elem_size;
+                T::from_bytes(&buf[start .. start + elem_size]).unwrap_or_else(|e| {
+                    panic!("Corrupt lump '{}': {}", info.name, e)
+                })
+            }).collect()
+        })
+    }
+
     pub fn get_metadata(&self) -> &WadMetadata { &self.meta }
 }
 
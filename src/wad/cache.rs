@@ -0,0 +1,156 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use sha3::{Digest, Sha3_256};
+
+use super::base::{FromBytes, ToBytes};
+
+pub trait CacheCodec: Sized {
+    fn to_cache_bytes(&self) -> Vec<u8>;
+    fn from_cache_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+// Lets `Archive::read_lump_cached` cache any `FromBytes + ToBytes` record type by
+// reusing its own binary encoding as the cache blob.
+impl<T: FromBytes + ToBytes> CacheCodec for Vec<T> {
+    fn to_cache_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.len() * T::size());
+        for item in self.iter() {
+            item.to_bytes(&mut out);
+        }
+        out
+    }
+
+    fn from_cache_bytes(bytes: &[u8]) -> Option<Vec<T>> {
+        let elem_size = T::size();
+        if elem_size == 0 || bytes.len() % elem_size != 0 {
+            return None;
+        }
+        let mut result = Vec::with_capacity(bytes.len() / elem_size);
+        for chunk in bytes.chunks(elem_size) {
+            match T::from_bytes(chunk) {
+                Ok(value) => result.push(value),
+                Err(_) => return None,
+            }
+        }
+        Some(result)
+    }
+}
+
+pub struct LumpCache {
+    dir: Option<PathBuf>,
+}
+
+impl LumpCache {
+    // The returned cache always misses, rather than failing the caller,
+    // if `dir` cannot be created.
+    pub fn open<P: AsRef<Path>>(dir: P) -> LumpCache {
+        match fs::create_dir_all(dir.as_ref()) {
+            Ok(()) => LumpCache { dir: Some(dir.as_ref().to_path_buf()) },
+            Err(_) => LumpCache { dir: None },
+        }
+    }
+
+    // Bumping `decoder_version` invalidates every artifact decoded by an
+    // older version, since it changes the cache key.
+    pub fn get_or_decode<T, F>(&self, hash_inputs: &[u8], decoder_version: u32,
+                                decode: F) -> T
+            where T: CacheCodec, F: FnOnce() -> T {
+        let path = self.path_for(hash_inputs, decoder_version);
+
+        if let Some(ref path) = path {
+            if let Some(cached) = read_cached::<T>(path) {
+                return cached;
+            }
+        }
+
+        let decoded = decode();
+        if let Some(ref path) = path {
+            let _ = write_cached(path, &decoded);
+        }
+        decoded
+    }
+
+    fn path_for(&self, hash_inputs: &[u8], decoder_version: u32) -> Option<PathBuf> {
+        let dir = match self.dir {
+            Some(ref dir) => dir,
+            None => return None,
+        };
+
+        let mut hasher = Sha3_256::new();
+        hasher.update(hash_inputs);
+        hasher.update(&[
+            (decoder_version & 0xff) as u8,
+            ((decoder_version >> 8) & 0xff) as u8,
+            ((decoder_version >> 16) & 0xff) as u8,
+            ((decoder_version >> 24) & 0xff) as u8,
+        ]);
+        let key: String = hasher.finalize().iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Some(dir.join(key))
+    }
+}
+
+fn read_cached<T: CacheCodec>(path: &Path) -> Option<T> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return None,
+    };
+    let mut bytes = Vec::new();
+    if file.read_to_end(&mut bytes).is_err() {
+        return None;
+    }
+    T::from_cache_bytes(&bytes)
+}
+
+// Writes to a sibling temp file and renames it into place, so a crash or
+// kill mid-write can never leave a truncated file at `path` for a later
+// `read_cached` to pick up.
+fn write_cached<T: CacheCodec>(path: &Path, value: &T) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = try!(File::create(&tmp_path));
+        try!(file.write_all(&value.to_cache_bytes()));
+    }
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn temp_cache_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(
+            format!("rust_doom_test_lump_cache_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_get_or_decode_caches_across_calls() {
+        let dir = temp_cache_dir("hit");
+        let cache = LumpCache::open(&dir);
+
+        let first: Vec<u8> = cache.get_or_decode(b"lump-bytes", 1, || vec![1, 2, 3]);
+        let second: Vec<u8> = cache.get_or_decode(b"lump-bytes", 1, || {
+            panic!("decode should not run again on a cache hit")
+        });
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_get_or_decode_misses_on_decoder_version_bump() {
+        let dir = temp_cache_dir("version-bump");
+        let cache = LumpCache::open(&dir);
+
+        let _: Vec<u8> = cache.get_or_decode(b"lump-bytes", 1, || vec![1, 2, 3]);
+        let bumped: Vec<u8> = cache.get_or_decode(b"lump-bytes", 2, || vec![4, 5, 6]);
+
+        assert_eq!(bumped, vec![4, 5, 6]);
+    }
+}
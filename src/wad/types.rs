@@ -0,0 +1,115 @@
+use std::fmt;
+
+use super::base::{BinaryResult, ByteBufferExt, FromBytes, ToBytes};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct WadName([u8; 8]);
+
+impl WadName {
+    pub fn canonicalise(&mut self) {
+        for byte in self.0.iter_mut() {
+            *byte = byte.to_ascii_uppercase();
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] { &self.0 }
+}
+
+impl fmt::Display for WadName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = self.0.iter().position(|&b| b == 0).unwrap_or(self.0.len());
+        write!(f, "{}", String::from_utf8_lossy(&self.0[..len]))
+    }
+}
+
+impl fmt::Debug for WadName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WadName({})", self)
+    }
+}
+
+pub trait WadNameCast {
+    fn to_wad_name(&self) -> WadName;
+}
+
+impl WadNameCast for [u8; 8] {
+    fn to_wad_name(&self) -> WadName { WadName(*self) }
+}
+
+impl FromBytes for WadName {
+    fn size() -> usize { 8 }
+
+    fn from_bytes(bytes: &[u8]) -> BinaryResult<WadName> {
+        let name = try!(bytes.c_name(0, 8));
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(name);
+        Ok(WadName(buf))
+    }
+}
+
+impl ToBytes for WadName {
+    fn to_bytes(&self, out: &mut Vec<u8>) { out.extend_from_slice(&self.0); }
+}
+
+pub struct WadInfo {
+    pub identification: [u8; 4],
+    pub num_lumps: i32,
+    pub info_table_offset: i32,
+}
+
+impl FromBytes for WadInfo {
+    fn size() -> usize { 12 }
+
+    fn from_bytes(bytes: &[u8]) -> BinaryResult<WadInfo> {
+        let id = try!(bytes.c_name(0, 4));
+        let mut identification = [0u8; 4];
+        identification.copy_from_slice(id);
+        Ok(WadInfo {
+            identification: identification,
+            num_lumps: try!(bytes.c_i32le(4)),
+            info_table_offset: try!(bytes.c_i32le(8)),
+        })
+    }
+}
+
+impl ToBytes for WadInfo {
+    fn to_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.identification);
+        write_i32le(out, self.num_lumps);
+        write_i32le(out, self.info_table_offset);
+    }
+}
+
+pub struct WadLump {
+    pub file_pos: i32,
+    pub size: i32,
+    pub name: WadName,
+}
+
+impl FromBytes for WadLump {
+    fn size() -> usize { 16 }
+
+    fn from_bytes(bytes: &[u8]) -> BinaryResult<WadLump> {
+        Ok(WadLump {
+            file_pos: try!(bytes.c_i32le(0)),
+            size: try!(bytes.c_i32le(4)),
+            name: try!(WadName::from_bytes(try!(bytes.c_name(8, 8)))),
+        })
+    }
+}
+
+impl ToBytes for WadLump {
+    fn to_bytes(&self, out: &mut Vec<u8>) {
+        write_i32le(out, self.file_pos);
+        write_i32le(out, self.size);
+        self.name.to_bytes(out);
+    }
+}
+
+fn write_i32le(out: &mut Vec<u8>, value: i32) {
+    let value = value as u32;
+    out.push((value & 0xff) as u8);
+    out.push(((value >> 8) & 0xff) as u8);
+    out.push(((value >> 16) & 0xff) as u8);
+    out.push(((value >> 24) & 0xff) as u8);
+}